@@ -3,39 +3,293 @@ use crate::codec::*;
 use crate::endpoint::Endpoint;
 use crate::error::ZmqResult;
 use crate::message::*;
+use crate::security::{SecureRecv, SecureSend, SecurityConfig};
 use crate::transport::AcceptStopHandle;
+use crate::trie::SubscriptionTrie;
 use crate::util::PeerIdentity;
-use crate::{
-    BlockingSend, MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketType, ZmqError,
-};
+use crate::{BlockingSend, MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketType};
 
 use async_trait::async_trait;
 use dashmap::DashMap;
 use futures::channel::{mpsc, oneshot};
-use futures::FutureExt;
+use futures::future::join_all;
+use futures::{FutureExt, SinkExt};
 use parking_lot::Mutex;
 use std::collections::HashMap;
-use std::io::ErrorKind;
-use std::pin::Pin;
 use std::sync::Arc;
 
+/// What to do with a publish once a subscriber's send high-water mark has
+/// been reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendHwmOverflow {
+    /// Discard the new message for that subscriber. This is the ZMQ PUB
+    /// default.
+    Drop,
+    /// Keep only the most recent message for that subscriber, replacing
+    /// any message still waiting to be sent. Ignores `send_hwm`, matching
+    /// ZMQ's `CONFLATE` option.
+    Conflate,
+    /// Wait for capacity to free up before accepting the message.
+    Block,
+}
+
+impl Default for SendHwmOverflow {
+    fn default() -> Self {
+        SendHwmOverflow::Drop
+    }
+}
+
+/// Configuration for a [`PubSocket`]/[`XPubSocket`], analogous to the
+/// `SocketOptions` builders used elsewhere in ZMQ socket implementations.
+#[derive(Clone)]
+pub struct PubSocketOptions {
+    send_hwm: usize,
+    send_hwm_overflow: SendHwmOverflow,
+    security: Option<SecurityConfig>,
+}
+
+impl Default for PubSocketOptions {
+    fn default() -> Self {
+        Self {
+            send_hwm: 1000,
+            send_hwm_overflow: SendHwmOverflow::Drop,
+            security: None,
+        }
+    }
+}
+
+impl PubSocketOptions {
+    /// Maximum number of messages queued per subscriber before
+    /// `send_hwm_overflow` kicks in. Ignored when the overflow policy is
+    /// `Conflate`.
+    pub fn send_hwm(&mut self, hwm: usize) -> &mut Self {
+        self.send_hwm = hwm;
+        self
+    }
+
+    pub fn send_hwm_overflow(&mut self, overflow: SendHwmOverflow) -> &mut Self {
+        self.send_hwm_overflow = overflow;
+        self
+    }
+
+    /// Require every peer to complete an authenticated handshake before
+    /// its subscriptions or publishes are accepted. See [`crate::security`].
+    pub fn security(&mut self, security: SecurityConfig) -> &mut Self {
+        self.security = Some(security);
+        self
+    }
+}
+
+/// The per-subscriber path messages take on their way to the wire. Publishes
+/// are handed off here rather than written to the transport directly so
+/// that a slow subscriber can't block (or be blocked by) the others. Cheap
+/// to clone so `publish`/`broadcast` can copy a subscriber's handle out of
+/// `subscribers` and enqueue on it without holding the map's guard across
+/// an await.
+#[derive(Clone)]
+enum Outbox {
+    /// Used by the `Drop` and `Block` overflow policies: a channel bounded
+    /// to the socket's send high-water mark, drained by a per-subscriber
+    /// forwarding task.
+    Bounded(mpsc::Sender<ZmqMessage>),
+    /// Used by the `Conflate` overflow policy: a single slot holding the
+    /// most recent message plus a doorbell to wake the forwarding task.
+    Conflated {
+        slot: Arc<Mutex<Option<ZmqMessage>>>,
+        doorbell: mpsc::Sender<()>,
+    },
+}
+
+/// The read half of a peer connection, either the raw transport or the
+/// encrypted channel produced by [`SecurityConfig::handshake`]. Lets the
+/// recv loop stay oblivious to whether security is configured.
+enum PeerRecv {
+    Plain(ZmqFramedRead),
+    Secure(SecureRecv),
+}
+
+impl PeerRecv {
+    async fn next_message(&mut self) -> Option<ZmqResult<Message>> {
+        use futures::StreamExt;
+        match self {
+            PeerRecv::Plain(recv) => recv.next().await,
+            PeerRecv::Secure(recv) => recv.next_message().await,
+        }
+    }
+}
+
+/// The write half of a peer connection, either the raw transport or the
+/// encrypted channel produced by [`SecurityConfig::handshake`]. Lets the
+/// outbox forwarding task stay oblivious to whether security is configured.
+enum PeerSend {
+    Plain(ZmqFramedWrite),
+    Secure(SecureSend),
+}
+
+impl PeerSend {
+    async fn send_message(&mut self, message: Message) -> ZmqResult<()> {
+        match self {
+            PeerSend::Plain(send) => send.send(message).await,
+            PeerSend::Secure(send) => send.send_message(message).await,
+        }
+    }
+}
+
 pub(crate) struct Subscriber {
-    pub(crate) subscriptions: Vec<Vec<u8>>,
-    pub(crate) send_queue: Pin<Box<ZmqFramedWrite>>,
+    outbox: Outbox,
     _subscription_coro_stop: oneshot::Sender<()>,
 }
 
 pub(crate) struct PubSocketBackend {
     subscribers: DashMap<PeerIdentity, Subscriber>,
+    /// Shared trie of every subscriber's topics, used to match publishes
+    /// in O(message length + matched peers) instead of scanning each
+    /// subscriber's subscription list.
+    subscriptions: Mutex<SubscriptionTrie>,
     socket_monitor: Mutex<Option<mpsc::Sender<SocketEvent>>>,
+    /// Sink that received frames are mirrored to, used by the XPUB/XSUB
+    /// flavours of this backend to surface frames through `SocketRecv`
+    /// instead of silently consuming them. Unused (and unpopulated) by
+    /// plain `PubSocket`.
+    recv_notify: Mutex<Option<mpsc::Sender<ZmqMessage>>>,
+    /// When set, every frame received from a peer is forwarded to
+    /// `recv_notify` verbatim instead of being interpreted as a
+    /// subscribe/unsubscribe control frame. This is XSUB's behaviour:
+    /// its peers are publishers, so inbound frames are published data,
+    /// not subscription updates.
+    passthrough: bool,
+    socket_type: SocketType,
+    options: PubSocketOptions,
+    /// Sink that a copy of every published message and every subscription
+    /// frame is mirrored to, set via `PubSocket::capture`. Used to build
+    /// proxy/tap devices and for observability of the publish stream
+    /// without instrumenting every call site that sends one.
+    capture: Mutex<Option<mpsc::Sender<ZmqMessage>>>,
 }
 
 impl PubSocketBackend {
-    fn message_received(&self, peer_id: &PeerIdentity, message: Message) {
+    pub(crate) fn with_recv_notify(
+        socket_type: SocketType,
+        recv_notify: Option<mpsc::Sender<ZmqMessage>>,
+        passthrough: bool,
+        options: PubSocketOptions,
+    ) -> Self {
+        Self {
+            subscribers: DashMap::new(),
+            subscriptions: Mutex::new(SubscriptionTrie::new()),
+            socket_monitor: Mutex::new(None),
+            recv_notify: Mutex::new(recv_notify),
+            passthrough,
+            socket_type,
+            options,
+            capture: Mutex::new(None),
+        }
+    }
+
+    /// The capture sink set via `PubSocket`/`XPubSocket`/`XSubSocket`'s
+    /// `capture` method, if any. Mirrors how `monitor` exposes
+    /// `socket_monitor` to those same callers.
+    ///
+    /// Every message handed to `capture` was mirrored here: each published
+    /// message, each subscribe/unsubscribe frame received from a peer, and
+    /// each frame an `XSubSocket` sends to its peers.
+    /// Building block for `zmq_proxy`-style steerable devices and for
+    /// recording or monitoring the publish stream without instrumenting
+    /// every `send` call site.
+    pub(crate) fn capture_sink(&self) -> &Mutex<Option<mpsc::Sender<ZmqMessage>>> {
+        &self.capture
+    }
+
+    /// Mirror `message` to the capture sink, if any.
+    fn capture(&self, message: &ZmqMessage) {
+        if let Some(sender) = self.capture_sink().lock().as_mut() {
+            let _ = sender.try_send(message.clone());
+        }
+    }
+
+    /// Enqueue `message` on `outbox`, honouring the socket's send
+    /// high-water mark and overflow policy. Takes the `Outbox` by
+    /// reference rather than the `Subscriber` it came from: callers clone
+    /// it out of `subscribers` first so this await can't hold the
+    /// `DashMap` shard guard hostage.
+    async fn enqueue(&self, outbox: &Outbox, message: ZmqMessage) {
+        match outbox {
+            Outbox::Bounded(sender) => match self.options.send_hwm_overflow {
+                SendHwmOverflow::Block => {
+                    let _ = sender.clone().send(message).await;
+                }
+                _ => {
+                    let _ = sender.clone().try_send(message);
+                }
+            },
+            Outbox::Conflated { slot, doorbell } => {
+                slot.lock().replace(message);
+                let _ = doorbell.clone().try_send(());
+            }
+        }
+    }
+
+    /// Spawn the task that drains a newly connected subscriber's `Outbox`
+    /// into its transport send queue, and return the `Outbox` handle used
+    /// to feed it.
+    fn spawn_outbox(self: &Arc<Self>, peer_id: PeerIdentity, mut send_queue: PeerSend) -> Outbox {
+        match self.options.send_hwm_overflow {
+            SendHwmOverflow::Conflate => {
+                let slot = Arc::new(Mutex::new(None::<ZmqMessage>));
+                let (doorbell, mut doorbell_rx) = mpsc::channel::<()>(1);
+                let task_slot = slot.clone();
+                let backend = self.clone();
+                async_rt::task::spawn(async move {
+                    use futures::StreamExt;
+                    while doorbell_rx.next().await.is_some() {
+                        let message = match task_slot.lock().take() {
+                            Some(message) => message,
+                            None => continue,
+                        };
+                        if send_queue
+                            .send_message(Message::Message(message))
+                            .await
+                            .is_err()
+                        {
+                            backend.peer_disconnected(&peer_id);
+                            break;
+                        }
+                    }
+                });
+                Outbox::Conflated { slot, doorbell }
+            }
+            SendHwmOverflow::Drop | SendHwmOverflow::Block => {
+                let (sender, mut receiver) = mpsc::channel::<ZmqMessage>(self.options.send_hwm);
+                let backend = self.clone();
+                async_rt::task::spawn(async move {
+                    use futures::StreamExt;
+                    while let Some(message) = receiver.next().await {
+                        if send_queue
+                            .send_message(Message::Message(message))
+                            .await
+                            .is_err()
+                        {
+                            backend.peer_disconnected(&peer_id);
+                            break;
+                        }
+                    }
+                });
+                Outbox::Bounded(sender)
+            }
+        }
+    }
+
+    pub(crate) fn message_received(&self, peer_id: &PeerIdentity, message: Message) {
         let message = match message {
             Message::Message(m) => m,
             _ => return,
         };
+        if self.passthrough {
+            if let Some(sender) = self.recv_notify.lock().as_mut() {
+                let _ = sender.try_send(message);
+            }
+            return;
+        }
         let data: Vec<u8> = message.into();
         if data.is_empty() {
             return;
@@ -43,80 +297,78 @@ impl PubSocketBackend {
         match data[0] {
             1 => {
                 // Subscribe
-                self.subscribers
-                    .get_mut(&peer_id)
-                    .unwrap()
-                    .subscriptions
-                    .push(Vec::from(&data[1..]));
+                self.subscriptions
+                    .lock()
+                    .subscribe(&data[1..], peer_id.clone());
+                let message = ZmqMessage::from(data);
+                self.capture(&message);
+                if let Some(sender) = self.recv_notify.lock().as_mut() {
+                    let _ = sender.try_send(message);
+                }
             }
             0 => {
                 // Unsubscribe
-                let mut del_index = None;
-                let sub = Vec::from(&data[1..]);
-                for (idx, subscription) in self
-                    .subscribers
-                    .get(&peer_id)
-                    .unwrap()
-                    .subscriptions
-                    .iter()
-                    .enumerate()
-                {
-                    if &sub == subscription {
-                        del_index = Some(idx);
-                        break;
-                    }
-                }
-                if let Some(index) = del_index {
-                    self.subscribers
-                        .get_mut(&peer_id)
-                        .unwrap()
-                        .subscriptions
-                        .remove(index);
+                self.subscriptions.lock().unsubscribe(&data[1..], peer_id);
+                let message = ZmqMessage::from(data);
+                self.capture(&message);
+                if let Some(sender) = self.recv_notify.lock().as_mut() {
+                    let _ = sender.try_send(message);
                 }
             }
             _ => (),
         }
     }
-}
-
-impl SocketBackend for PubSocketBackend {
-    fn socket_type(&self) -> SocketType {
-        SocketType::PUB
-    }
-
-    fn shutdown(&self) {
-        self.subscribers.clear();
-    }
 
-    fn monitor(&self) -> &Mutex<Option<mpsc::Sender<SocketEvent>>> {
-        &self.socket_monitor
+    /// Publish `message` to every subscriber whose subscription set
+    /// matches its topic prefix. Shared by `PubSocket` and `XPubSocket`.
+    pub(crate) async fn publish(&self, message: ZmqMessage) -> ZmqResult<()> {
+        self.capture(&message);
+        let matched_peers = self.subscriptions.lock().matching_peers(&message.data);
+        // Clone each matched subscriber's outbox handle and drop the map
+        // guard before awaiting: `enqueue` can suspend (e.g. `Block`
+        // overflow), and holding a `DashMap` guard across that await would
+        // stall every other peer sharing its shard, not just this one.
+        let outboxes: Vec<Outbox> = matched_peers
+            .iter()
+            .filter_map(|peer_id| self.subscribers.get(peer_id).map(|s| s.outbox.clone()))
+            .collect();
+        join_all(
+            outboxes
+                .iter()
+                .map(|outbox| self.enqueue(outbox, message.clone())),
+        )
+        .await;
+        Ok(())
     }
-}
 
-impl MultiPeerBackend for PubSocketBackend {
-    fn peer_connected(self: Arc<Self>, peer_id: &PeerIdentity, io: FramedIo) {
-        let (mut recv_queue, send_queue) = io.into_parts();
-        // TODO provide handling for recv_queue
+    /// Register a peer whose transport (plain or, once handshaked,
+    /// encrypted) is ready to use, and spawn the tasks that drive it.
+    /// Shared by the plaintext and [`SecurityConfig::handshake`] paths in
+    /// `peer_connected`.
+    fn finish_peer_connected(
+        self: Arc<Self>,
+        peer_id: PeerIdentity,
+        mut recv_queue: PeerRecv,
+        send_queue: PeerSend,
+    ) {
         let (sender, stop_receiver) = oneshot::channel();
+        let outbox = self.spawn_outbox(peer_id.clone(), send_queue);
         self.subscribers.insert(
             peer_id.clone(),
             Subscriber {
-                subscriptions: vec![],
-                send_queue: Box::pin(send_queue),
+                outbox,
                 _subscription_coro_stop: sender,
             },
         );
         let backend = self;
-        let peer_id = peer_id.clone();
         async_rt::task::spawn(async move {
-            use futures::StreamExt;
             let mut stop_receiver = stop_receiver.fuse();
             loop {
                 futures::select! {
                      _ = stop_receiver => {
                          break;
                      },
-                     message = recv_queue.next().fuse() => {
+                     message = recv_queue.next_message().fuse() => {
                         match message {
                             Some(Ok(m)) => backend.message_received(&peer_id, m),
                             Some(Err(e)) => {
@@ -136,9 +388,98 @@ impl MultiPeerBackend for PubSocketBackend {
         });
     }
 
+    /// Forward `message` to every connected peer unconditionally, ignoring
+    /// subscription filters. Used by `XSubSocket::send` to relay
+    /// subscribe/unsubscribe control frames upstream to the publishers it
+    /// is connected to.
+    pub(crate) async fn broadcast(&self, message: ZmqMessage) -> ZmqResult<()> {
+        self.capture(&message);
+        // See `publish`: clone the outboxes out before awaiting any of
+        // them so one slow peer can't hold up delivery to the rest.
+        let outboxes: Vec<Outbox> = self.subscribers.iter().map(|s| s.outbox.clone()).collect();
+        join_all(
+            outboxes
+                .iter()
+                .map(|outbox| self.enqueue(outbox, message.clone())),
+        )
+        .await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl PubSocketBackend {
+    /// Register a subscriber backed by a channel the test can read from
+    /// directly, bypassing the real transport and handshake/recv-loop
+    /// machinery `peer_connected` normally sets up. Used by `XSubSocket`'s
+    /// tests to observe `broadcast` without a live connection.
+    pub(crate) fn insert_test_subscriber(
+        &self,
+        peer_id: PeerIdentity,
+    ) -> mpsc::Receiver<ZmqMessage> {
+        let (sender, receiver) = mpsc::channel(16);
+        let (stop, _stop_receiver) = oneshot::channel();
+        self.subscribers.insert(
+            peer_id,
+            Subscriber {
+                outbox: Outbox::Bounded(sender),
+                _subscription_coro_stop: stop,
+            },
+        );
+        receiver
+    }
+}
+
+impl SocketBackend for PubSocketBackend {
+    fn socket_type(&self) -> SocketType {
+        self.socket_type
+    }
+
+    fn shutdown(&self) {
+        self.subscribers.clear();
+        *self.subscriptions.lock() = SubscriptionTrie::new();
+    }
+
+    fn monitor(&self) -> &Mutex<Option<mpsc::Sender<SocketEvent>>> {
+        &self.socket_monitor
+    }
+}
+
+impl MultiPeerBackend for PubSocketBackend {
+    fn peer_connected(self: Arc<Self>, peer_id: &PeerIdentity, io: FramedIo) {
+        match self.options.security.clone() {
+            None => {
+                let (recv_queue, send_queue) = io.into_parts();
+                self.finish_peer_connected(
+                    peer_id.clone(),
+                    PeerRecv::Plain(recv_queue),
+                    PeerSend::Plain(send_queue),
+                );
+            }
+            Some(security) => {
+                let backend = self;
+                let peer_id = peer_id.clone();
+                async_rt::task::spawn(async move {
+                    match security.handshake(io).await {
+                        Ok((recv, send)) => backend.finish_peer_connected(
+                            peer_id,
+                            PeerRecv::Secure(recv),
+                            PeerSend::Secure(send),
+                        ),
+                        Err(e) => {
+                            log::warn!("Handshake with {:?} failed: {:?}", peer_id, e);
+                            backend.peer_disconnected(&peer_id);
+                        }
+                    }
+                });
+            }
+        }
+    }
+
     fn peer_disconnected(&self, peer_id: &PeerIdentity) {
         log::info!("Client disconnected {:?}", peer_id);
         self.subscribers.remove(peer_id);
+        self.subscriptions.lock().remove_peer(peer_id);
     }
 }
 
@@ -156,49 +497,37 @@ impl Drop for PubSocket {
 #[async_trait]
 impl BlockingSend for PubSocket {
     async fn send(&mut self, message: ZmqMessage) -> ZmqResult<()> {
-        let mut dead_peers = Vec::new();
-        for mut subscriber in self.backend.subscribers.iter_mut() {
-            for sub_filter in &subscriber.subscriptions {
-                if sub_filter.as_slice() == &message.data[0..sub_filter.len()] {
-                    let res = subscriber
-                        .send_queue
-                        .as_mut()
-                        .try_send(Message::Message(message.clone()));
-                    match res {
-                        Ok(()) => {}
-                        Err(ZmqError::Codec(CodecError::Io(e))) => {
-                            if e.kind() == ErrorKind::BrokenPipe {
-                                dead_peers.push(subscriber.key().clone());
-                            } else {
-                                dbg!(e);
-                            }
-                        }
-                        Err(e) => {
-                            dbg!(e);
-                            todo!()
-                        }
-                    }
-                    break;
-                }
-            }
-        }
-        for peer in dead_peers {
-            self.backend.peer_disconnected(&peer);
+        self.backend.publish(message).await
+    }
+}
+
+impl PubSocket {
+    /// Construct a `PubSocket` with non-default send high-water mark /
+    /// overflow behaviour.
+    pub fn with_options(options: PubSocketOptions) -> Self {
+        Self {
+            backend: Arc::new(PubSocketBackend::with_recv_notify(
+                SocketType::PUB,
+                None,
+                false,
+                options,
+            )),
+            binds: HashMap::new(),
         }
-        Ok(())
+    }
+
+    /// See [`PubSocketBackend::capture_sink`].
+    pub fn capture(&mut self) -> mpsc::Receiver<ZmqMessage> {
+        let (sender, receiver) = mpsc::channel(1024);
+        self.backend.capture_sink().lock().replace(sender);
+        receiver
     }
 }
 
 #[async_trait]
 impl Socket for PubSocket {
     fn new() -> Self {
-        Self {
-            backend: Arc::new(PubSocketBackend {
-                subscribers: DashMap::new(),
-                socket_monitor: Mutex::new(None),
-            }),
-            binds: HashMap::new(),
-        }
+        Self::with_options(PubSocketOptions::default())
     }
 
     fn backend(&self) -> Arc<dyn MultiPeerBackend> {
@@ -223,6 +552,7 @@ mod tests {
         test_bind_to_any_port_helper, test_bind_to_unspecified_interface_helper,
     };
     use crate::ZmqResult;
+    use futures::StreamExt;
     use std::net::IpAddr;
 
     #[tokio::test]
@@ -244,4 +574,78 @@ mod tests {
         let s = PubSocket::new();
         test_bind_to_unspecified_interface_helper(any_ipv6, s, 4010).await
     }
+
+    fn test_backend(overflow: SendHwmOverflow) -> PubSocketBackend {
+        let mut options = PubSocketOptions::default();
+        options.send_hwm_overflow(overflow);
+        PubSocketBackend::with_recv_notify(SocketType::PUB, None, false, options)
+    }
+
+    #[tokio::test]
+    async fn drop_overflow_discards_once_the_channel_is_full() {
+        let backend = test_backend(SendHwmOverflow::Drop);
+        let (sender, mut receiver) = mpsc::channel::<ZmqMessage>(0);
+        let outbox = Outbox::Bounded(sender);
+
+        backend.enqueue(&outbox, ZmqMessage::from(vec![1])).await;
+        backend.enqueue(&outbox, ZmqMessage::from(vec![2])).await;
+        drop(outbox);
+
+        let mut received = Vec::new();
+        while let Some(message) = receiver.next().await {
+            received.push(message);
+        }
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].data, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn conflate_overflow_keeps_only_the_most_recent_message() {
+        let backend = test_backend(SendHwmOverflow::Conflate);
+        let slot = Arc::new(Mutex::new(None::<ZmqMessage>));
+        let (doorbell, _doorbell_rx) = mpsc::channel::<()>(1);
+        let outbox = Outbox::Conflated {
+            slot: slot.clone(),
+            doorbell,
+        };
+
+        backend.enqueue(&outbox, ZmqMessage::from(vec![1])).await;
+        backend.enqueue(&outbox, ZmqMessage::from(vec![2])).await;
+
+        assert_eq!(slot.lock().as_ref().unwrap().data, vec![2]);
+    }
+
+    #[tokio::test]
+    async fn block_overflow_waits_for_capacity_instead_of_dropping() {
+        let backend = Arc::new(test_backend(SendHwmOverflow::Block));
+        let (sender, mut receiver) = mpsc::channel::<ZmqMessage>(0);
+        let outbox = Outbox::Bounded(sender);
+
+        // Fill the channel's one slot so the next enqueue has to wait.
+        backend.enqueue(&outbox, ZmqMessage::from(vec![1])).await;
+
+        let (completed_tx, mut completed_rx) = oneshot::channel::<()>();
+        let task_backend = backend.clone();
+        let task_outbox = outbox.clone();
+        async_rt::task::spawn(async move {
+            task_backend
+                .enqueue(&task_outbox, ZmqMessage::from(vec![2]))
+                .await;
+            let _ = completed_tx.send(());
+        });
+
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert_eq!(
+            completed_rx.try_recv().unwrap(),
+            None,
+            "enqueue should still be waiting for capacity, not have dropped the message"
+        );
+
+        let first = receiver.next().await.unwrap();
+        assert_eq!(first.data, vec![1]);
+
+        completed_rx.await.unwrap();
+        let second = receiver.next().await.unwrap();
+        assert_eq!(second.data, vec![2]);
+    }
 }
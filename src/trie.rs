@@ -0,0 +1,181 @@
+use crate::util::PeerIdentity;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Default)]
+struct TrieNode {
+    children: HashMap<u8, TrieNode>,
+    /// Peers whose subscription terminates exactly at this node, with a
+    /// per-peer reference count so that duplicate subscribes from the same
+    /// peer require an equal number of unsubscribes before interest drops.
+    peers: HashMap<PeerIdentity, usize>,
+}
+
+impl TrieNode {
+    fn is_empty(&self) -> bool {
+        self.peers.is_empty() && self.children.is_empty()
+    }
+}
+
+/// A prefix trie mapping subscribed topics to the peers subscribed to them,
+/// shared across all subscribers of a `PubSocketBackend`. Matching a
+/// published message's topic against every subscriber's subscription list
+/// is O(peers * subscriptions); walking this trie along the message bytes
+/// is O(message length + matched peers) instead, since subscribers with
+/// the same or overlapping prefixes share trie nodes.
+#[derive(Default)]
+pub(crate) struct SubscriptionTrie {
+    root: TrieNode,
+}
+
+impl SubscriptionTrie {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that `peer` subscribed to `topic`.
+    pub(crate) fn subscribe(&mut self, topic: &[u8], peer: PeerIdentity) {
+        let mut node = &mut self.root;
+        for &byte in topic {
+            node = node.children.entry(byte).or_default();
+        }
+        *node.peers.entry(peer).or_insert(0) += 1;
+    }
+
+    /// Record that `peer` unsubscribed from `topic`, pruning trie nodes
+    /// that no longer carry any interest.
+    pub(crate) fn unsubscribe(&mut self, topic: &[u8], peer: &PeerIdentity) {
+        Self::unsubscribe_at(&mut self.root, topic, peer);
+    }
+
+    // Returns whether `node` became empty and can be dropped by its parent.
+    fn unsubscribe_at(node: &mut TrieNode, topic: &[u8], peer: &PeerIdentity) -> bool {
+        match topic.split_first() {
+            None => {
+                if let Some(count) = node.peers.get_mut(peer) {
+                    *count -= 1;
+                    if *count == 0 {
+                        node.peers.remove(peer);
+                    }
+                }
+            }
+            Some((&byte, rest)) => {
+                if let Some(child) = node.children.get_mut(&byte) {
+                    if Self::unsubscribe_at(child, rest, peer) {
+                        node.children.remove(&byte);
+                    }
+                }
+            }
+        }
+        node.is_empty()
+    }
+
+    /// Remove every subscription held by `peer`, wherever it appears in
+    /// the trie. Called when a peer disconnects.
+    pub(crate) fn remove_peer(&mut self, peer: &PeerIdentity) {
+        Self::remove_peer_at(&mut self.root, peer);
+    }
+
+    fn remove_peer_at(node: &mut TrieNode, peer: &PeerIdentity) -> bool {
+        node.peers.remove(peer);
+        node.children
+            .retain(|_, child| !Self::remove_peer_at(child, peer));
+        node.is_empty()
+    }
+
+    /// Every peer whose subscription matches a prefix of `data`, including
+    /// peers subscribed to the empty prefix (which matches everything).
+    pub(crate) fn matching_peers(&self, data: &[u8]) -> HashSet<PeerIdentity> {
+        let mut matched = HashSet::new();
+        let mut node = &self.root;
+        matched.extend(node.peers.keys().cloned());
+        for byte in data {
+            match node.children.get(byte) {
+                Some(child) => {
+                    node = child;
+                    matched.extend(node.peers.keys().cloned());
+                }
+                None => break,
+            }
+        }
+        matched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duplicate_subscribes_require_matching_unsubscribes() {
+        let mut trie = SubscriptionTrie::default();
+        let peer = PeerIdentity::default();
+
+        trie.subscribe(b"topic", peer.clone());
+        trie.subscribe(b"topic", peer.clone());
+
+        trie.unsubscribe(b"topic", &peer);
+        assert!(
+            trie.matching_peers(b"topic").contains(&peer),
+            "one unsubscribe should not drop interest after two subscribes"
+        );
+
+        trie.unsubscribe(b"topic", &peer);
+        assert!(
+            !trie.matching_peers(b"topic").contains(&peer),
+            "the matching unsubscribe should drop interest"
+        );
+    }
+
+    #[test]
+    fn unsubscribe_only_affects_its_own_prefix() {
+        let mut trie = SubscriptionTrie::default();
+        let peer = PeerIdentity::default();
+
+        trie.subscribe(b"a", peer.clone());
+        trie.subscribe(b"ab", peer.clone());
+
+        trie.unsubscribe(b"a", &peer);
+
+        assert!(!trie.matching_peers(b"a").contains(&peer));
+        assert!(trie.matching_peers(b"ab").contains(&peer));
+    }
+
+    #[test]
+    fn remove_peer_drops_every_subscription_but_leaves_others() {
+        let mut trie = SubscriptionTrie::default();
+        let peer = PeerIdentity::default();
+        let other = PeerIdentity::default();
+
+        trie.subscribe(b"a", peer.clone());
+        trie.subscribe(b"b", peer.clone());
+        trie.subscribe(b"a", other.clone());
+
+        trie.remove_peer(&peer);
+
+        assert!(!trie.matching_peers(b"a").contains(&peer));
+        assert!(!trie.matching_peers(b"b").contains(&peer));
+        assert!(trie.matching_peers(b"a").contains(&other));
+    }
+
+    #[test]
+    fn empty_prefix_subscription_matches_every_topic() {
+        let mut trie = SubscriptionTrie::default();
+        let peer = PeerIdentity::default();
+
+        trie.subscribe(b"", peer.clone());
+
+        assert!(trie.matching_peers(b"").contains(&peer));
+        assert!(trie.matching_peers(b"anything").contains(&peer));
+    }
+
+    #[test]
+    fn matching_peers_only_returns_prefix_matches() {
+        let mut trie = SubscriptionTrie::default();
+        let peer = PeerIdentity::default();
+
+        trie.subscribe(b"news.sports", peer.clone());
+
+        assert!(!trie.matching_peers(b"news").contains(&peer));
+        assert!(trie.matching_peers(b"news.sports.football").contains(&peer));
+    }
+}
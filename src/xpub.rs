@@ -0,0 +1,128 @@
+use crate::endpoint::Endpoint;
+use crate::error::ZmqResult;
+use crate::message::*;
+use crate::pub_socket::{PubSocketBackend, PubSocketOptions};
+use crate::transport::AcceptStopHandle;
+use crate::{
+    BlockingSend, MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketRecv, SocketType,
+    ZmqError,
+};
+
+use async_trait::async_trait;
+use futures::channel::mpsc;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A PUB socket that, unlike plain `PubSocket`, surfaces subscribe
+/// (`\x01<topic>`) and unsubscribe (`\x00<topic>`) frames from its
+/// subscribers through `SocketRecv` instead of silently consuming them.
+/// This makes it possible to build forwarding/proxy devices that need to
+/// see who subscribed to what.
+pub struct XPubSocket {
+    pub(crate) backend: Arc<PubSocketBackend>,
+    binds: HashMap<Endpoint, AcceptStopHandle>,
+    recv_queue: mpsc::Receiver<ZmqMessage>,
+}
+
+impl Drop for XPubSocket {
+    fn drop(&mut self) {
+        self.backend.shutdown();
+    }
+}
+
+#[async_trait]
+impl BlockingSend for XPubSocket {
+    async fn send(&mut self, message: ZmqMessage) -> ZmqResult<()> {
+        self.backend.publish(message).await
+    }
+}
+
+#[async_trait]
+impl SocketRecv for XPubSocket {
+    async fn recv(&mut self) -> ZmqResult<ZmqMessage> {
+        self.recv_queue.next().await.ok_or(ZmqError::NoMessage)
+    }
+}
+
+impl XPubSocket {
+    /// Construct an `XPubSocket` with non-default send high-water mark /
+    /// overflow / security options.
+    pub fn with_options(options: PubSocketOptions) -> Self {
+        let (sender, receiver) = mpsc::channel(1024);
+        Self {
+            backend: Arc::new(PubSocketBackend::with_recv_notify(
+                SocketType::XPUB,
+                Some(sender),
+                false,
+                options,
+            )),
+            binds: HashMap::new(),
+            recv_queue: receiver,
+        }
+    }
+
+    /// See [`PubSocketBackend::capture_sink`].
+    pub fn capture(&mut self) -> mpsc::Receiver<ZmqMessage> {
+        let (sender, receiver) = mpsc::channel(1024);
+        self.backend.capture_sink().lock().replace(sender);
+        receiver
+    }
+}
+
+#[async_trait]
+impl Socket for XPubSocket {
+    fn new() -> Self {
+        Self::with_options(PubSocketOptions::default())
+    }
+
+    fn backend(&self) -> Arc<dyn MultiPeerBackend> {
+        self.backend.clone()
+    }
+
+    fn binds(&mut self) -> &mut HashMap<Endpoint, AcceptStopHandle> {
+        &mut self.binds
+    }
+
+    fn monitor(&mut self) -> mpsc::Receiver<SocketEvent> {
+        let (sender, receiver) = mpsc::channel(1024);
+        self.backend.monitor().lock().replace(sender);
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::PeerIdentity;
+
+    #[tokio::test]
+    async fn recv_surfaces_a_subscribe_frame_from_a_peer() {
+        let mut socket = XPubSocket::new();
+        let peer_id = PeerIdentity::default();
+        let mut topic = vec![1u8];
+        topic.extend_from_slice(b"news");
+
+        socket
+            .backend
+            .message_received(&peer_id, Message::Message(ZmqMessage::from(topic.clone())));
+
+        let received = socket.recv().await.unwrap();
+        assert_eq!(Vec::<u8>::from(received), topic);
+    }
+
+    #[tokio::test]
+    async fn recv_surfaces_an_unsubscribe_frame_from_a_peer() {
+        let mut socket = XPubSocket::new();
+        let peer_id = PeerIdentity::default();
+        let mut topic = vec![0u8];
+        topic.extend_from_slice(b"news");
+
+        socket
+            .backend
+            .message_received(&peer_id, Message::Message(ZmqMessage::from(topic.clone())));
+
+        let received = socket.recv().await.unwrap();
+        assert_eq!(Vec::<u8>::from(received), topic);
+    }
+}
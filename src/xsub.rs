@@ -0,0 +1,121 @@
+use crate::endpoint::Endpoint;
+use crate::error::ZmqResult;
+use crate::message::*;
+use crate::pub_socket::{PubSocketBackend, PubSocketOptions};
+use crate::transport::AcceptStopHandle;
+use crate::{
+    BlockingSend, MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketRecv, SocketType,
+    ZmqError,
+};
+
+use async_trait::async_trait;
+use futures::channel::mpsc;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A SUB socket that, unlike plain `SubSocket`, forwards every message
+/// passed to `send` (the `\x01<topic>` / `\x00<topic>` subscribe and
+/// unsubscribe control frames) to its connected peers instead of refusing
+/// to send, and surfaces published payloads received from those peers
+/// through `SocketRecv`. Pairing an `XSubSocket` with an `XPubSocket` lets
+/// an intermediary forward subscriptions and data without understanding
+/// the topics flowing through it.
+pub struct XSubSocket {
+    pub(crate) backend: Arc<PubSocketBackend>,
+    binds: HashMap<Endpoint, AcceptStopHandle>,
+    recv_queue: mpsc::Receiver<ZmqMessage>,
+}
+
+impl Drop for XSubSocket {
+    fn drop(&mut self) {
+        self.backend.shutdown();
+    }
+}
+
+#[async_trait]
+impl BlockingSend for XSubSocket {
+    async fn send(&mut self, message: ZmqMessage) -> ZmqResult<()> {
+        self.backend.broadcast(message).await
+    }
+}
+
+#[async_trait]
+impl SocketRecv for XSubSocket {
+    async fn recv(&mut self) -> ZmqResult<ZmqMessage> {
+        self.recv_queue.next().await.ok_or(ZmqError::NoMessage)
+    }
+}
+
+impl XSubSocket {
+    /// Construct an `XSubSocket` with non-default send high-water mark /
+    /// overflow / security options.
+    pub fn with_options(options: PubSocketOptions) -> Self {
+        let (sender, receiver) = mpsc::channel(1024);
+        Self {
+            backend: Arc::new(PubSocketBackend::with_recv_notify(
+                SocketType::XSUB,
+                Some(sender),
+                true,
+                options,
+            )),
+            binds: HashMap::new(),
+            recv_queue: receiver,
+        }
+    }
+
+    /// See [`PubSocketBackend::capture_sink`].
+    pub fn capture(&mut self) -> mpsc::Receiver<ZmqMessage> {
+        let (sender, receiver) = mpsc::channel(1024);
+        self.backend.capture_sink().lock().replace(sender);
+        receiver
+    }
+}
+
+#[async_trait]
+impl Socket for XSubSocket {
+    fn new() -> Self {
+        Self::with_options(PubSocketOptions::default())
+    }
+
+    fn backend(&self) -> Arc<dyn MultiPeerBackend> {
+        self.backend.clone()
+    }
+
+    fn binds(&mut self) -> &mut HashMap<Endpoint, AcceptStopHandle> {
+        &mut self.binds
+    }
+
+    fn monitor(&mut self) -> mpsc::Receiver<SocketEvent> {
+        let (sender, receiver) = mpsc::channel(1024);
+        self.backend.monitor().lock().replace(sender);
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::PeerIdentity;
+
+    #[tokio::test]
+    async fn send_forwards_to_every_connected_peer_unconditionally() {
+        let mut socket = XSubSocket::new();
+        let mut subscribed = socket
+            .backend
+            .insert_test_subscriber(PeerIdentity::default());
+        let mut unsubscribed = socket
+            .backend
+            .insert_test_subscriber(PeerIdentity::default());
+
+        let mut topic = vec![1u8];
+        topic.extend_from_slice(b"news");
+        socket.send(ZmqMessage::from(topic.clone())).await.unwrap();
+
+        // broadcast() ignores the subscription trie entirely, so both
+        // peers receive the frame even though neither has subscribed to
+        // anything on this backend.
+        assert_eq!(Vec::<u8>::from(subscribed.next().await.unwrap()), topic);
+        assert_eq!(Vec::<u8>::from(unsubscribed.next().await.unwrap()), topic);
+    }
+}